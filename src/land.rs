@@ -2,12 +2,74 @@ use crate::tic80::*;
 use crate::trace;
 use itertools::Itertools;
 use noise::{Simplex, NoiseFn};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
 
 // Common internal functions
 fn sigmoid(x: f64) -> f64 {
     1.0 / (1.0 + f64::exp(-x))
 }
 
+/// Advance a CRC-16/CCITT (poly 0x1021) checksum by one byte
+fn crc16_update(crc: u16, byte: u8) -> u16 {
+    let mut crc = crc ^ ((byte as u16) << 8);
+    for _ in 0..8 {
+        crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+    }
+    crc
+}
+
+/// Alphabet used by `base64_encode`/`base64_decode` (the standard base64 alphabet)
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as a compact, clipboard-safe ASCII string
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for group in data.chunks(3) {
+        let b0 = group[0] as u32;
+        let b1 = *group.get(1).unwrap_or(&0) as u32;
+        let b2 = *group.get(2).unwrap_or(&0) as u32;
+        let n = (b0 << 16) | (b1 << 8) | b2;
+        out.push(BASE64_ALPHABET[((n >> 18) & 0x3f) as usize] as char);
+        out.push(BASE64_ALPHABET[((n >> 12) & 0x3f) as usize] as char);
+        out.push(if group.len() > 1 { BASE64_ALPHABET[((n >> 6) & 0x3f) as usize] as char } else { '=' });
+        out.push(if group.len() > 2 { BASE64_ALPHABET[(n & 0x3f) as usize] as char } else { '=' });
+    }
+    out
+}
+
+/// Decode a string produced by `base64_encode`, or `None` if it isn't valid
+fn base64_decode(code: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+    let bytes = code.as_bytes();
+    if bytes.is_empty() || bytes.len() % 4 != 0 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(bytes.len() / 4 * 3);
+    for group in bytes.chunks(4) {
+        let pad = group.iter().filter(|&&c| c == b'=').count();
+        let mut vals = [0u32; 4];
+        for (i, &c) in group.iter().enumerate() {
+            vals[i] = if c == b'=' { 0 } else { value(c)? };
+        }
+        let n = (vals[0] << 18) | (vals[1] << 12) | (vals[2] << 6) | vals[3];
+        out.push((n >> 16) as u8);
+        if pad < 2 { out.push((n >> 8) as u8); }
+        if pad < 1 { out.push(n as u8); }
+    }
+    Some(out)
+}
+
 /// Struct to describe a land chunk with size of 8x8 pixels,
 /// which is stored as a u64 value in 8 sequential MAP cells 
 pub struct LandChunk {
@@ -66,25 +128,30 @@ impl LandChunk {
         self.get_mask() == !0u64
     }
 
-    /// Draw the chunk with at `(x, y)` a given tile texture ID and scale factor
-    pub fn draw(&self, where_x: i32, where_y:i32, tile: i32, scale: i32) {
-        if !self.empty() {
-            if self.full() {
+    /// Draw the chunk at `(where_x, where_y)`, picking each chunk-local pixel's tile via
+    /// `tile_at(x, y)`. Falls back to the fast uniform `spr` path when every visible pixel
+    /// resolves to the same tile (e.g. a chunk that lies entirely within one texture stratum)
+    pub fn draw(&self, where_x: i32, where_y: i32, scale: i32, tile_at: impl Fn(i32, i32) -> i32) {
+        if self.empty() {
+            return;
+        }
+        if self.full() {
+            let tile = tile_at(0, 0);
+            if (0..8).cartesian_product(0..8).all(|(x, y)| tile_at(x, y) == tile) {
                 // Just use spr() for optimized rendering
-                // spr(tile, where_x, where_y, SpriteOptions::default());
                 spr(tile, where_x, where_y, SpriteOptions{scale, ..Default::default()});
-            } else {
-                // Draw every chunk pixel manually
-                unsafe {
+                return;
+            }
+        }
+        // Draw every chunk pixel manually
+        unsafe {
+            for (x, y) in (0..8).cartesian_product(0..8) {
+                if self.get(x, y) {
+                    let tile = tile_at(x, y);
                     let tile_addr4 = (TILES as i32 + tile * 32) * 2;
-                    for (x, y ) in (0..8).cartesian_product(0..8) {                      
-                        if self.get(x, y) {
-                            // Gather the tile's pixel colors and put it on the screen
-                            let color = peek4(tile_addr4 + y * 8 + x);
-                            // pix(where_x + x, where_y + y, color);
-                            rect(where_x + x * scale, where_y + y * scale, scale, scale, color);
-                        }
-                    }
+                    // Gather the tile's pixel colors and put it on the screen
+                    let color = peek4(tile_addr4 + y * 8 + x);
+                    rect(where_x + x * scale, where_y + y * scale, scale, scale, color);
                 }
             }
         }
@@ -92,6 +159,7 @@ impl LandChunk {
 }
 
 /// Land texture description
+#[derive(Clone, Copy)]
 pub struct LandTexture {
     pub spr_id: i32, // ID of the first texture sprite/tile
     pub width: i32,  // Texture width
@@ -105,8 +173,116 @@ impl LandTexture {
     }
 }
 
+/// One band of the depth-based terrain texture: `texture` is used for pixels whose depth
+/// below the local surface is at least `depth` pixels, until a deeper stratum takes over
+#[derive(Clone, Copy)]
+pub struct LandStratum {
+    pub texture: LandTexture,
+    pub depth: i32,
+}
+
+/// Maximum number of strata that fit in the MAP header
+const MAX_STRATA: usize = 4;
+
+/// Address of the two-byte CRC-16 checksum within the MAP header. NOTE: this reuses byte
+/// offset 8, which used to be the `covered` flag; a MAP saved by a cart built before this
+/// checksum existed has unrelated data there, fails the checksum check, and is discarded in
+/// favor of a freshly generated land by `from_map_or_new` rather than loading it. Expected
+/// and harmless pre-release, but worth knowing before shipping a save-compatible update.
+const CHECKSUM_ADDR: i32 = 8;
+
+/// Address where the strata list starts within the MAP header
+const STRATA_ADDR: i32 = 10;
+
 /// Chunk address offest for reservation
-const LAND_CHUNK_ADDR_RESERVE: i32 = 0x10;
+const LAND_CHUNK_ADDR_RESERVE: i32 = 0x20;
+
+/// TIC-80 screen size in pixels
+const SCREEN_W: i32 = 240;
+const SCREEN_H: i32 = 137;
+
+/// Color used to mark on-screen cells that fall outside the land rectangle
+const BOUNDARY_COLOR: i32 = 0;
+
+/// Tracks which pixels have changed since a cache was last recomputed, so the recompute can
+/// touch only the affected area instead of the whole land (e.g. terrain falling one pixel per
+/// frame would otherwise force a full recompute every single frame). `full` subsumes any `rect`
+/// and is used for edits that touch the whole land, like `clear`/`generate`/`import_code`.
+#[derive(Clone, Copy)]
+struct Dirty {
+    full: bool,
+    rect: Option<(i32, i32, i32, i32)>, // Inclusive (min_x, min_y, max_x, max_y) touched so far
+}
+
+impl Dirty {
+    fn new() -> Self {
+        Self{full: true, rect: None} // Nothing cached yet: the first use must compute everything
+    }
+
+    fn clean() -> Self {
+        Self{full: false, rect: None}
+    }
+
+    fn mark_full(&mut self) {
+        self.full = true;
+        self.rect = None;
+    }
+
+    fn mark_rect(&mut self, x0: i32, y0: i32, x1: i32, y1: i32) {
+        if self.full {
+            return;
+        }
+        self.rect = Some(match self.rect {
+            Some((a, b, c, d)) => (a.min(x0), b.min(y0), c.max(x1), d.max(y1)),
+            None => (x0, y0, x1, y1),
+        });
+    }
+}
+
+/// Cached result of the water flood-fill, recomputed only over the area marked dirty
+/// since terrain actually changed there
+struct WaterFill {
+    dirty: Dirty,
+    mask: Vec<bool>,
+}
+
+impl WaterFill {
+    fn new() -> Self {
+        Self{dirty: Dirty::new(), mask: Vec::new()}
+    }
+}
+
+/// Cached per-column surface height (topmost solid pixel row), recomputed only over columns
+/// marked dirty since terrain actually changed there; used to pick a texture stratum by depth
+struct SurfaceHeights {
+    dirty: Dirty,
+    height: Vec<i32>,
+}
+
+impl SurfaceHeights {
+    fn new() -> Self {
+        Self{dirty: Dirty::new(), height: Vec::new()}
+    }
+}
+
+/// A solid component bordering a recent edit whose connectivity to grounded terrain hasn't
+/// been fully traced yet. `advance_grounded_checks` resumes its flood fill a little each frame
+/// instead of tracing it to completion (or giving up) in one shot, so a single large edit can't
+/// spike one frame's cost
+struct PendingComponent {
+    component: Vec<(i32, i32)>, // Pixels confirmed to belong to this component so far
+    queue: VecDeque<(i32, i32)>, // Frontier still left to explore
+}
+
+/// Who currently owns a solid pixel that's part of a disconnected-terrain trace: a component
+/// already confirmed loose and falling, or a still-being-traced `PendingComponent`, identified
+/// by id rather than position since two traces can merge into one mid-flood (see
+/// `advance_grounded_checks`), which would otherwise leave stale indices behind
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Claim {
+    Loose,
+    Pending(u32),
+}
 
 // WARNING: Total land size (width * height * 8) may not exceed 32,640 bytes (the map memory size),
 // as the map memory is used to load/share lands
@@ -115,16 +291,30 @@ pub struct Land {
     height: i32,   // Land total height in chuncks
     seed: u32,     // Seed used to generate the land
     covered: bool, // Covered land flag
-    texture: LandTexture,
+    strata: Vec<LandStratum>, // Depth-ordered texture bands, shallowest first
     water_height: i32,
+    water_fill: RefCell<WaterFill>,
+    cave_density: f64, // Amount of interior cave/ravine carving, from 0.0 (none) to 1.0 (swiss cheese)
+    surface_heights: RefCell<SurfaceHeights>,
+    loose: RefCell<Vec<Vec<(i32, i32)>>>, // Solid components that lost support and are falling
+    pending: RefCell<HashMap<u32, PendingComponent>>, // Components whose groundedness isn't settled yet
+    next_pending_id: Cell<u32>, // Next id to hand out to a new PendingComponent
+    claimed: RefCell<HashMap<(i32, i32), Claim>>, // Owner of every pixel in `loose` or `pending`
 }
 
 impl Land {
     /// Empty land constructor
-    pub fn new(width: i32, height: i32, texture: LandTexture) -> Self {
+    pub fn new(width: i32, height: i32, strata: Vec<LandStratum>) -> Self {
         assert!(width * height * 8 + LAND_CHUNK_ADDR_RESERVE <= 32640);
+        assert!(!strata.is_empty() && strata.len() <= MAX_STRATA);
+        let mut strata = strata;
+        strata.sort_by_key(|stratum| stratum.depth);
         let water_height = height * 8 - 8;
-        let land = Self{width, height, texture, water_height, seed: 0, covered: false};
+        let land = Self{width, height, strata, water_height, seed: 0, covered: false,
+            water_fill: RefCell::new(WaterFill::new()), cave_density: 0.0,
+            surface_heights: RefCell::new(SurfaceHeights::new()), loose: RefCell::new(Vec::new()),
+            pending: RefCell::new(HashMap::new()), next_pending_id: Cell::new(0),
+            claimed: RefCell::new(HashMap::new())};
         land.save_in_map();
         return land;
     }
@@ -137,29 +327,43 @@ impl Land {
         for i in 0..4 {
             seed = (seed << 8) | (mget(2 + i, 0) as u32);
         }
-        let texture = LandTexture {
-            spr_id: mget(6, 0),
-            width: mget(7, 0) >> 4, 
-            height: mget(7, 0) & 0x0f
-        };
+        let covered = (mget(6, 0) & 0x01) != 0;
+        let strata_count = mget(7, 0).clamp(1, MAX_STRATA as i32);
+        let mut strata = Vec::with_capacity(strata_count as usize);
+        for i in 0..strata_count {
+            let base = STRATA_ADDR + i * 3;
+            let texture = LandTexture {
+                spr_id: mget(base + 1, 0),
+                width: mget(base + 2, 0) >> 4,
+                height: mget(base + 2, 0) & 0x0f,
+            };
+            strata.push(LandStratum{texture, depth: mget(base, 0)});
+        }
         let water_height = height * 8 - 8;
-        let covered = (mget(8, 0) & 0x01) != 0;
-        Self{width, height, texture, water_height, seed, covered}
+        Self{width, height, strata, water_height, seed, covered,
+            water_fill: RefCell::new(WaterFill::new()), cave_density: 0.0,
+            surface_heights: RefCell::new(SurfaceHeights::new()), loose: RefCell::new(Vec::new()),
+            pending: RefCell::new(HashMap::new()), next_pending_id: Cell::new(0),
+            claimed: RefCell::new(HashMap::new())}
     }
 
-    /// Construct a land from MAP data unless they are invalid.
+    /// Construct a land from MAP data unless they are invalid or corrupt.
     /// Otherwise make an empty land from the given arguments.
-    pub fn from_map_or_new(width: i32, height: i32, texture: LandTexture) -> Self {
-        // Data check (TODO: use CRC for better validation)
+    pub fn from_map_or_new(width: i32, height: i32, strata: Vec<LandStratum>) -> Self {
         let (map_width, map_height) = (mget(0, 0), mget(1, 0));
         if map_width == 0 || map_height == 0 {
-            Self::new(width, height, texture)
-        } else {
-            Self::from_map()
+            return Self::new(width, height, strata);
         }
+        let land = Self::from_map();
+        let stored = ((mget(CHECKSUM_ADDR, 0) as u16) << 8) | (mget(CHECKSUM_ADDR + 1, 0) as u16);
+        if land.compute_checksum() != stored {
+            return Self::new(width, height, strata);
+        }
+        land
     }
 
-    /// Save data in the MAP memory
+    /// Save data in the MAP memory, including a checksum covering the header and the
+    /// full land pixel region so `from_map_or_new` can detect corrupt or foreign data
     pub fn save_in_map(&self) {
         mset(0, 0, self.width & 0xff);
         mset(1, 0, self.height & 0xff);
@@ -167,11 +371,135 @@ impl Land {
             let val = (self.seed >> (i * 8)) as i32;
             mset(5 - i, 0, val & 0xff);
         }
-        let texture_size = (self.texture.width << 4) | self.texture.height;
-        let flags= self.covered as i32;
-        mset(6, 0, self.texture.spr_id);
-        mset(7, 0, texture_size);
-        mset(8, 0, flags);
+        mset(6, 0, self.covered as i32);
+        mset(7, 0, self.strata.len() as i32);
+        for (i, stratum) in self.strata.iter().enumerate() {
+            let base = STRATA_ADDR + (i as i32) * 3;
+            let size = (stratum.texture.width << 4) | stratum.texture.height;
+            mset(base, 0, stratum.depth.clamp(0, 255));
+            mset(base + 1, 0, stratum.texture.spr_id);
+            mset(base + 2, 0, size);
+        }
+        let checksum = self.compute_checksum();
+        mset(CHECKSUM_ADDR, 0, (checksum >> 8) as i32 & 0xff);
+        mset(CHECKSUM_ADDR + 1, 0, checksum as i32 & 0xff);
+    }
+
+    /// Compute a CRC-16 checksum over the header (excluding the checksum field itself) and
+    /// the full land pixel region, used to detect corrupt or foreign cartridge data on load
+    fn compute_checksum(&self) -> u16 {
+        let mut crc: u16 = 0xffff;
+        for addr in 0..(STRATA_ADDR + self.strata.len() as i32 * 3) {
+            if addr != CHECKSUM_ADDR && addr != CHECKSUM_ADDR + 1 {
+                crc = crc16_update(crc, mget(addr, 0) as u8);
+            }
+        }
+        for addr in LAND_CHUNK_ADDR_RESERVE..(LAND_CHUNK_ADDR_RESERVE + self.width * self.height * 8) {
+            crc = crc16_update(crc, mget(addr % 240, addr / 240) as u8);
+        }
+        crc
+    }
+
+    /// Export this land as a compact ASCII code that can be copied out and pasted back in
+    /// with `import_code` to reproduce an exact (possibly edited) map on another machine.
+    /// NOTE: `Land` never surfaces this itself; a host needs its own UI/input hook (printing
+    /// to the console, a button binding, etc.) to actually expose sharing to the player
+    pub fn export_code(&self) -> String {
+        let mut data = Vec::new();
+        for i in 0..4 {
+            data.push((self.seed >> ((3 - i) * 8)) as u8);
+        }
+        data.push(self.width as u8);
+        data.push(self.height as u8);
+        data.push(self.covered as u8);
+        data.push(self.strata.len() as u8);
+        for stratum in &self.strata {
+            data.push(stratum.depth.clamp(0, 255) as u8);
+            data.push(stratum.texture.spr_id as u8);
+            data.push(((stratum.texture.width << 4) | stratum.texture.height) as u8);
+        }
+        // Run-length encode the chunk masks: long runs of identical (often empty/full)
+        // chunks are extremely common and collapse to a handful of bytes
+        let masks: Vec<u64> = self.chunk_coordinates()
+            .filter_map(|(x, y)| self.chunk(x, y).map(|chunk| chunk.get_mask()))
+            .collect();
+        let mut i = 0;
+        while i < masks.len() {
+            let mask = masks[i];
+            let mut run = 1usize;
+            while i + run < masks.len() && masks[i + run] == mask && run < 0xffff {
+                run += 1;
+            }
+            data.extend_from_slice(&(run as u16).to_be_bytes());
+            data.extend_from_slice(&mask.to_be_bytes());
+            i += run;
+        }
+        base64_encode(&data)
+    }
+
+    /// Rebuild a land from a code produced by `export_code`, or `None` if it's malformed
+    pub fn import_code(code: &str) -> Option<Self> {
+        let data = base64_decode(code)?;
+        if data.len() < 8 {
+            return None;
+        }
+        let mut seed = 0u32;
+        for i in 0..4 {
+            seed = (seed << 8) | (data[i] as u32);
+        }
+        let width = data[4] as i32;
+        let height = data[5] as i32;
+        let covered = data[6] != 0;
+        let strata_count = (data[7] as usize).clamp(1, MAX_STRATA);
+        let mut pos = 8;
+        let mut strata = Vec::with_capacity(strata_count);
+        for _ in 0..strata_count {
+            if pos + 3 > data.len() {
+                return None;
+            }
+            let texture = LandTexture {
+                spr_id: data[pos + 1] as i32,
+                width: (data[pos + 2] >> 4) as i32,
+                height: (data[pos + 2] & 0x0f) as i32,
+            };
+            strata.push(LandStratum{texture, depth: data[pos] as i32});
+            pos += 3;
+        }
+        if width <= 0 || height <= 0 || width * height * 8 + LAND_CHUNK_ADDR_RESERVE > 32640 {
+            return None;
+        }
+
+        let mut land = Self::new(width, height, strata);
+        land.set_seed(seed);
+        land.covered = covered;
+
+        let coords: Vec<(i32, i32)> = land.chunk_coordinates().collect();
+        let mut idx = 0;
+        while pos + 10 <= data.len() && idx < coords.len() {
+            let run = u16::from_be_bytes(data[pos..pos + 2].try_into().unwrap()) as usize;
+            let mask = u64::from_be_bytes(data[pos + 2..pos + 10].try_into().unwrap());
+            pos += 10;
+            for _ in 0..run {
+                if idx >= coords.len() {
+                    break;
+                }
+                let (x, y) = coords[idx];
+                if let Some(chunk) = land.chunk(x, y) {
+                    // `get_mask` packs row 0 into the most significant byte, while `set_mask`
+                    // takes row 0 from the least significant one
+                    chunk.set_mask(mask.swap_bytes());
+                }
+                idx += 1;
+            }
+        }
+        // A truncated/corrupt code wouldn't cover every chunk; reject it rather than leaving
+        // the tail of the new land as whatever stale bytes were already sitting in MAP memory
+        if idx != coords.len() || pos != data.len() {
+            return None;
+        }
+        land.mark_terrain_dirty_full();
+        land.save_in_map();
+        Some(land)
     }
 
     /// Return size of the land in pixels
@@ -213,9 +541,26 @@ impl Land {
     pub fn set(&self, x: i32, y: i32, state: bool) {
         if let Some(chunk) = self.chunk(x, y) {
             chunk.set(x % 8, y % 8, state);
+            self.mark_terrain_dirty(x, y);
         }
     }
 
+    /// Mark pixel `(x, y)` as changed, forcing the cached water fill and surface heights to
+    /// recompute that area before they're next used. Edits that touch many scattered pixels
+    /// (a dig circle, a falling component settling) can call this once per pixel cheaply: the
+    /// dirty area just grows to cover all of them, rather than forcing a full recompute
+    fn mark_terrain_dirty(&self, x: i32, y: i32) {
+        self.water_fill.borrow_mut().dirty.mark_rect(x, y, x, y);
+        self.surface_heights.borrow_mut().dirty.mark_rect(x, y, x, y);
+    }
+
+    /// Mark the entire terrain as changed, e.g. after a full regeneration or import where
+    /// tracking the precise touched area isn't worth it
+    fn mark_terrain_dirty_full(&self) {
+        self.water_fill.borrow_mut().dirty.mark_full();
+        self.surface_heights.borrow_mut().dirty.mark_full();
+    }
+
     /// Set the state of pixels inside a circle
     pub fn set_circle(&self, x: i32, y: i32, r: i32, state: bool) {
         let r2 = r * r;
@@ -224,6 +569,165 @@ impl Land {
                 self.set(x + i, y + j, state);
             }
         }
+        if !state {
+            // Digging may have cut the support from under overhangs or islands
+            self.update_loose_components(x - r - 1, y - r - 1, x + r + 1, y + r + 1);
+        }
+    }
+
+    /// Upper bound on how many pixels `advance_grounded_checks` traces per frame, spread across
+    /// every component still awaiting a verdict. Keeps a single frame cheap even while a large
+    /// component (e.g. one bordering the main landmass, which is enormous) is still being
+    /// traced; unlike a one-shot budget this never has to guess, it just takes more frames.
+    const LOOSE_FLOOD_BUDGET: usize = 512;
+
+    /// Find solid components bordering the just-edited `(x0, y0)-(x1, y1)` rectangle that
+    /// aren't already known to be grounded or loose, and start tracing their connectivity to
+    /// grounded terrain via `advance_grounded_checks`. Each becomes its own `PendingComponent`
+    /// for now; `advance_grounded_checks` merges any of these that turn out to be mutually
+    /// connected, so seeding them separately here doesn't fragment a single structure. Cells
+    /// already owned by a loose or pending component are skipped.
+    fn update_loose_components(&self, x0: i32, y0: i32, x1: i32, y1: i32) {
+        for x in (x0 - 1)..=(x1 + 1) {
+            for y in (y0 - 1)..=(y1 + 1) {
+                if self.in_bounds(x, y) && self.get(x, y) && !self.claimed.borrow().contains_key(&(x, y)) {
+                    let id = self.next_pending_id.get();
+                    self.next_pending_id.set(id + 1);
+                    self.claimed.borrow_mut().insert((x, y), Claim::Pending(id));
+                    self.pending.borrow_mut().insert(id, PendingComponent {
+                        component: vec![(x, y)],
+                        queue: VecDeque::from([(x, y)]),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Resume tracing every `PendingComponent`'s connectivity to grounded terrain, spending at
+    /// most `LOOSE_FLOOD_BUDGET` pixels total this frame. A component that reaches the ground
+    /// is released (it was attached all along); one that exhausts its frontier without ever
+    /// reaching the ground is confirmed loose and handed to `step_physics`. A component that
+    /// merely runs out of this frame's budget is left pending and resumes next frame, so a
+    /// large genuinely-loose component is never mistaken for grounded just because tracing it
+    /// takes more than one frame.
+    ///
+    /// Two pending components can border each other (e.g. `update_loose_components` seeds one
+    /// pixel per border cell, so a dig spanning a thin bridge seeds many of them along its rim).
+    /// When one's frontier bumps into a cell already claimed by a *different* pending component,
+    /// the two are merged into a single trace rather than treating the cell as an impassable
+    /// wall - otherwise neither trace can route through the other's territory to reach the
+    /// grounded end, and a structure that's attached at both ends gets wrongly split into
+    /// several pieces that all look disconnected.
+    fn advance_grounded_checks(&self) {
+        let (land_w, land_h) = self.size();
+        let mut pending = self.pending.borrow_mut();
+        let mut budget = Self::LOOSE_FLOOD_BUDGET;
+        let ids: Vec<u32> = pending.keys().copied().collect();
+        for id in ids {
+            if budget == 0 {
+                break;
+            }
+            // May already have been absorbed into another component earlier in this loop
+            if !pending.contains_key(&id) {
+                continue;
+            }
+            let mut grounded = false;
+            while budget > 0 {
+                let next = pending.get_mut(&id).unwrap().queue.pop_front();
+                let (x, y) = match next {
+                    Some(cell) => cell,
+                    None => break,
+                };
+                budget -= 1;
+                if y == land_h - 1 || (self.covered && (x == 0 || x == land_w - 1)) {
+                    grounded = true;
+                    break;
+                }
+                for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                    if !self.in_bounds(nx, ny) || !self.get(nx, ny) {
+                        continue;
+                    }
+                    let owner = self.claimed.borrow().get(&(nx, ny)).copied();
+                    match owner {
+                        None => {
+                            self.claimed.borrow_mut().insert((nx, ny), Claim::Pending(id));
+                            let comp = pending.get_mut(&id).unwrap();
+                            comp.component.push((nx, ny));
+                            comp.queue.push_back((nx, ny));
+                        }
+                        Some(Claim::Pending(other)) if other != id => {
+                            if let Some(mut absorbed) = pending.remove(&other) {
+                                let mut claimed = self.claimed.borrow_mut();
+                                for &cell in &absorbed.component {
+                                    claimed.insert(cell, Claim::Pending(id));
+                                }
+                                drop(claimed);
+                                let comp = pending.get_mut(&id).unwrap();
+                                comp.component.append(&mut absorbed.component);
+                                comp.queue.append(&mut absorbed.queue);
+                            }
+                        }
+                        _ => {} // Already ours, or a currently-falling loose component: a wall
+                    }
+                }
+            }
+            if grounded {
+                if let Some(attached) = pending.remove(&id) {
+                    let mut claimed = self.claimed.borrow_mut();
+                    for cell in attached.component {
+                        claimed.remove(&cell);
+                    }
+                }
+            } else if pending.get(&id).is_some_and(|p| p.queue.is_empty()) {
+                if let Some(confirmed_loose) = pending.remove(&id) {
+                    let mut claimed = self.claimed.borrow_mut();
+                    for &cell in &confirmed_loose.component {
+                        claimed.insert(cell, Claim::Loose);
+                    }
+                    drop(claimed);
+                    self.loose.borrow_mut().push(confirmed_loose.component);
+                }
+            } // Otherwise still undetermined; pick up where we left off next frame
+        }
+    }
+
+    /// Advance falling components by one pixel, settling them back into the static terrain
+    /// once they hit the ground or another obstacle. Call once per frame.
+    pub fn step_physics(&self) {
+        self.advance_grounded_checks();
+        let mut loose = self.loose.borrow_mut();
+        let land_h = self.height * 8;
+        let mut i = 0;
+        while i < loose.len() {
+            let pixels: HashSet<(i32, i32)> = loose[i].iter().cloned().collect();
+            let can_fall = loose[i].iter().all(|&(x, y)| {
+                let below = y + 1;
+                below < land_h && (pixels.contains(&(x, below)) || !self.get(x, below))
+            });
+            if can_fall {
+                // Move the claims along with the pixels so they keep tracking the component
+                let mut claimed = self.claimed.borrow_mut();
+                for &(x, y) in loose[i].iter() {
+                    self.set(x, y, false);
+                    claimed.remove(&(x, y));
+                }
+                for (_, y) in loose[i].iter_mut() {
+                    *y += 1;
+                }
+                for &(x, y) in loose[i].iter() {
+                    self.set(x, y, true);
+                    claimed.insert((x, y), Claim::Loose);
+                }
+                i += 1;
+            } else {
+                // Settled: already resting in its final position, merge back into the static
+                // terrain and release its claim so a future edit can detect it going loose again
+                let settled = loose.remove(i);
+                for cell in settled {
+                    self.claimed.borrow_mut().remove(&cell);
+                }
+            }
+        }
     }
 
     /// Return an iterator over the two dimensions of land with the step of 8 (chunk size)
@@ -233,25 +737,235 @@ impl Land {
         return x_range.cartesian_product(y_range);
     }
 
+    /// Compute the visible land-pixel window `(x0, y0, x1, y1)` (exclusive upper bounds)
+    /// covered by the screen at the given camera offset and scale
+    fn visible_window(&self, offset_x: i32, offset_y: i32, scale: i32) -> (i32, i32, i32, i32) {
+        let (land_w, land_h) = self.size();
+        let x0 = ((-offset_x) / scale).clamp(0, land_w);
+        let y0 = ((-offset_y) / scale).clamp(0, land_h);
+        let x1 = ((SCREEN_W - offset_x + scale - 1) / scale).clamp(0, land_w);
+        let y1 = ((SCREEN_H - offset_y + scale - 1) / scale).clamp(0, land_h);
+        (x0, y0, x1, y1)
+    }
+
+    /// Compute the clamped top-left camera offset that centers the view on `(focus_x, focus_y)`,
+    /// stopping at the land edges instead of scrolling past them
+    pub fn camera_offset(&self, focus_x: i32, focus_y: i32, screen_w: i32, screen_h: i32) -> (i32, i32) {
+        let (land_w, land_h) = self.size();
+        let center = |focus: i32, screen: i32, land: i32| -> i32 {
+            if land <= screen {
+                -(screen - land) / 2 // Land smaller than the screen: center it instead of clamping
+            } else {
+                (focus - screen / 2).clamp(0, land - screen)
+            }
+        };
+        (center(focus_x, screen_w, land_w), center(focus_y, screen_h, land_h))
+    }
+
     /// Draw the land and water
     pub fn draw(&self, offset_x: i32, offset_y: i32, scale: i32) {
-        // Land chunks
-        for (x, y) in self.chunk_coordinates() {
+        let (land_w, _) = self.size();
+        // Mark the on-screen area outside the land rectangle so the edge of the world is visible
+        let (land_left, land_top) = (offset_x, offset_y);
+        let (land_right, land_bottom) = (offset_x + land_w * scale, offset_y + self.height * 8 * scale);
+        if land_left > 0 { rect(0, 0, land_left, SCREEN_H, BOUNDARY_COLOR); }
+        if land_right < SCREEN_W { rect(land_right, 0, SCREEN_W - land_right, SCREEN_H, BOUNDARY_COLOR); }
+        if land_top > 0 { rect(0, 0, SCREEN_W, land_top, BOUNDARY_COLOR); }
+        if land_bottom < SCREEN_H { rect(0, land_bottom, SCREEN_W, SCREEN_H - land_bottom, BOUNDARY_COLOR); }
+
+        // Only the chunks that can land on screen need to be visited
+        self.recompute_surface_heights();
+        let surface_heights = self.surface_heights.borrow();
+        let (vis_x0, vis_y0, vis_x1, vis_y1) = self.visible_window(offset_x, offset_y, scale);
+        let (chunk_x0, chunk_y0) = (vis_x0 / 8, vis_y0 / 8);
+        let (chunk_x1, chunk_y1) = ((vis_x1 + 7) / 8, (vis_y1 + 7) / 8);
+        for (cx, cy) in (chunk_x0..chunk_x1).cartesian_product(chunk_y0..chunk_y1) {
+            let (x, y) = (cx * 8, cy * 8);
             if let Some(chunk) = self.chunk(x, y) {
                 let where_x = offset_x + x * scale;
                 let where_y = offset_y + y * scale;
-                let tile = self.texture.tile(x / 8, y / 8);
-                chunk.draw(where_x, where_y, tile, scale);
+                let tile_at = |lx: i32, ly: i32| {
+                    let depth = (y + ly) - surface_heights.height[(x + lx) as usize];
+                    self.stratum_at(depth).tile(cx, cy)
+                };
+                chunk.draw(where_x, where_y, scale, tile_at);
             }
         }
-        // Water
-        let water_height = offset_y + self.water_height;
-        if water_height < 137 {
-            let water_depth = 137 - water_height;
-            rect(0, water_height, 240, water_depth, 10);
+
+        // Water: pixels flood-filled from the open sea, recomputed only over the area that
+        // actually changed since the last recompute
+        self.recompute_water_fill();
+        let water_fill = self.water_fill.borrow();
+        let water_y0 = vis_y0.max(self.water_height);
+        for y in water_y0..vis_y1 {
+            for x in vis_x0..vis_x1 {
+                if water_fill.mask[(y * land_w + x) as usize] {
+                    rect(offset_x + x * scale, offset_y + y * scale, scale, scale, 10);
+                }
+            }
         }
     }
-    
+
+    /// Recompute the cached water mask with a 4-connected BFS from the open sea, touching only
+    /// the area marked dirty since the last recompute (the whole land the first time, or after
+    /// a full regeneration/import). Outside that area the cached mask is assumed to still be a
+    /// fixed point of the flood fill, so it's used as both a wall (still solid/dry) and a source
+    /// (still water) at the area's border - *unless* the padded span's BFS actually reaches that
+    /// border at a cell that isn't water yet, meaning the edit opened a new connection into
+    /// territory this pass never re-examined (e.g. a dug tunnel linking open water to a
+    /// previously sealed cavern more than a pixel past the edit). In that case the incremental
+    /// assumption doesn't hold, so this falls back to a full recompute instead of leaving
+    /// newly-reachable cells wrongly cached dry.
+    fn recompute_water_fill(&self) {
+        let (land_w, land_h) = self.size();
+        let mut water_fill = self.water_fill.borrow_mut();
+        let (mut full, rect) = (water_fill.dirty.full, water_fill.dirty.rect);
+        water_fill.dirty = Dirty::clean();
+        if !full && rect.is_none() {
+            return; // Nothing touched since the last recompute
+        }
+        // Reuse the existing buffer instead of reallocating it every time a dig marks the
+        // fill dirty, which would otherwise happen on every frame of a held-down drag
+        let mask = &mut water_fill.mask;
+        mask.resize((land_w * land_h) as usize, false);
+
+        let seed = |x: i32, y: i32, mask: &mut Vec<bool>, queue: &mut VecDeque<(i32, i32)>| {
+            if y >= self.water_height && self.in_bounds(x, y) && !self.get(x, y) {
+                let idx = (y * land_w + x) as usize;
+                if !mask[idx] {
+                    mask[idx] = true;
+                    queue.push_back((x, y));
+                }
+            }
+        };
+
+        loop {
+            let mut queue: VecDeque<(i32, i32)> = VecDeque::new();
+            // The span of the grid this recompute is responsible for: the whole land when dirty,
+            // or the touched rect padded by one pixel so its untouched border can seed inward
+            let (rx0, ry0, rx1, ry1) = if full {
+                mask.iter_mut().for_each(|b| *b = false);
+                (0, self.water_height, land_w - 1, land_h - 1)
+            } else {
+                let (x0, y0, x1, y1) = rect.unwrap();
+                let (rx0, ry0) = ((x0 - 1).max(0), (y0 - 1).max(self.water_height));
+                let (rx1, ry1) = ((x1 + 1).min(land_w - 1), (y1 + 1).min(land_h - 1));
+                // Clear just the touched region so water that's no longer reachable is dropped,
+                // then feed back in whatever water was already sitting just outside it
+                for y in ry0..=ry1 {
+                    for x in rx0..=rx1 {
+                        mask[(y * land_w + x) as usize] = false;
+                    }
+                }
+                for x in rx0..=rx1 {
+                    for &y in &[ry0 - 1, ry1 + 1] {
+                        if y >= self.water_height && y < land_h && mask[(y * land_w + x) as usize] {
+                            queue.push_back((x, y));
+                        }
+                    }
+                }
+                for y in ry0..=ry1 {
+                    for &x in &[rx0 - 1, rx1 + 1] {
+                        if x >= 0 && x < land_w && mask[(y * land_w + x) as usize] {
+                            queue.push_back((x, y));
+                        }
+                    }
+                }
+                (rx0, ry0, rx1, ry1)
+            };
+
+            // Open sea at the waterline, within the recomputed span
+            if ry0 <= self.water_height {
+                for x in rx0..=rx1 {
+                    seed(x, self.water_height, mask, &mut queue);
+                }
+            }
+            // Off-board left/right columns at or below the waterline also feed the sea inward,
+            // unless the land is covered, in which case the side walls are sealed (see `get`)
+            if !self.covered {
+                if rx0 == 0 {
+                    for y in ry0..=ry1 { seed(0, y, mask, &mut queue); }
+                }
+                if rx1 == land_w - 1 {
+                    for y in ry0..=ry1 { seed(land_w - 1, y, mask, &mut queue); }
+                }
+            }
+
+            let mut escaped = false;
+            while let Some((x, y)) = queue.pop_front() {
+                for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                    if ny < self.water_height {
+                        continue; // Water can't climb above sea level
+                    }
+                    if nx < rx0 || nx > rx1 || ny < ry0 || ny > ry1 {
+                        // Outside the padded span. If this cell is passable and isn't already
+                        // cached as water, the flood genuinely reaches past where this pass
+                        // looked, so the "cache is still valid out there" assumption is broken.
+                        if self.in_bounds(nx, ny) && !self.get(nx, ny)
+                            && !mask[(ny * land_w + nx) as usize] {
+                            escaped = true;
+                        }
+                        continue;
+                    }
+                    seed(nx, ny, mask, &mut queue);
+                }
+                if escaped {
+                    break;
+                }
+            }
+
+            if escaped && !full {
+                full = true; // Redo from scratch; a dry run past the span can't be trusted
+                continue;
+            }
+            break;
+        }
+    }
+
+    /// Recompute the per-column surface height (topmost solid pixel row), touching only the
+    /// columns marked dirty since the last recompute
+    fn recompute_surface_heights(&self) {
+        let (land_w, land_h) = self.size();
+        let mut surface_heights = self.surface_heights.borrow_mut();
+        let (full, rect) = (surface_heights.dirty.full, surface_heights.dirty.rect);
+        surface_heights.dirty = Dirty::clean();
+        if surface_heights.height.len() != land_w as usize {
+            surface_heights.height = vec![land_h; land_w as usize];
+        }
+        if !full && rect.is_none() {
+            return; // Nothing touched since the last recompute
+        }
+        let (x0, x1) = if full {
+            (0, land_w - 1)
+        } else {
+            let (rx0, _, rx1, _) = rect.unwrap();
+            ((rx0 - 1).max(0), (rx1 + 1).min(land_w - 1))
+        };
+        for x in x0..=x1 {
+            let mut h = land_h;
+            for y in 0..land_h {
+                if self.get(x, y) {
+                    h = y;
+                    break;
+                }
+            }
+            surface_heights.height[x as usize] = h;
+        }
+    }
+
+    /// Pick the deepest stratum whose threshold is at or above `depth` pixels below the surface
+    fn stratum_at(&self, depth: i32) -> &LandTexture {
+        let mut texture = &self.strata[0].texture;
+        for stratum in &self.strata {
+            if stratum.depth <= depth {
+                texture = &stratum.texture;
+            } else {
+                break;
+            }
+        }
+        texture
+    }
+
     /// Clear the entire land
     pub fn clear(&self) {
         for (x, y) in self.chunk_coordinates() {
@@ -259,6 +973,13 @@ impl Land {
                 chunk.set_mask(0);
             }
         }
+        self.mark_terrain_dirty_full();
+        // Any component still mid-fall or mid-trace from a previous dig no longer means
+        // anything once the terrain underneath it has been wiped and rebuilt; forget it rather
+        // than having step_physics() apply its stale coordinates to the freshly generated land
+        self.loose.borrow_mut().clear();
+        self.pending.borrow_mut().clear();
+        self.claimed.borrow_mut().clear();
     }
 
     /// Function that suppresses altitude at the land board.
@@ -284,12 +1005,21 @@ impl Land {
         self.seed = seed;
     }
 
+    /// Set the cave/ravine carving density, from `0.0` (none) to `1.0` (swiss cheese);
+    /// call `generate()` again afterwards to see the effect. NOTE: `Land` never calls this
+    /// itself, so a host that never calls it (or never calls `generate()` afterwards) gets a
+    /// solid land with no caves at all, not a sensible default density
+    pub fn set_cave_density(&mut self, cave_density: f64) {
+        self.cave_density = cave_density;
+    }
+
     /// Generate a random land using Simplex noise
     pub fn generate(&self) {
         self.clear();
         let (land_w, land_h) = self.size();
         let land_h_f64 = (self.water_height - 2) as f64;
         let simplex = Simplex::new(self.seed);
+        let mut surface_y = vec![land_h; land_w as usize];
         for x in 0..land_w {
             let x_norm = (x as f64) / (land_w as f64);
             let (k1, k2) = (3.0 + simplex.get([2.0, -1.0]), 5.0 + simplex.get([-1.0, 2.0]));
@@ -299,9 +1029,230 @@ impl Land {
             let constrain = Land::board_constrain(x_norm, board_w);
             let h = land_h_f64 * (1.0 - (h0 * constrain));
             let y_start = std::cmp::min(h as i32, land_h - 1);
+            surface_y[x as usize] = y_start;
             for y in y_start..land_h {
                 self.set(x, y, true);
             }
         }
+        if self.cave_density > 0.0 {
+            self.carve_caves(&surface_y);
+        }
+    }
+
+    /// Hollow out caves and winding ravines below the surface using a ridged noise field,
+    /// leaving a thin solid crust just under the surface row so it doesn't turn to swiss cheese
+    fn carve_caves(&self, surface_y: &[i32]) {
+        const SURFACE_CRUST: i32 = 2;
+        let (land_w, land_h) = self.size();
+        let cave_simplex = Simplex::new(self.seed ^ 0x9e37_79b9);
+        let threshold = 1.0 - self.cave_density.clamp(0.0, 1.0);
+        for x in 0..land_w {
+            let carve_start = surface_y[x as usize] + SURFACE_CRUST;
+            for y in carve_start..land_h {
+                // Broad chambers from a low-frequency octave, thin tunnels from a high-frequency one
+                let chambers = 1.0 - cave_simplex.get([x as f64 / 14.0, y as f64 / 14.0]).abs();
+                let tunnels = 1.0 - cave_simplex.get([x as f64 / 4.0, y as f64 / 4.0]).abs();
+                let n = 0.65 * chambers + 0.35 * tunnels;
+                if n > threshold {
+                    self.set(x, y, false);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_matches_known_vector() {
+        // "123456789" is the standard CRC-16/CCITT-FALSE test vector (init 0xffff, poly 0x1021)
+        let mut crc = 0xffffu16;
+        for &byte in b"123456789" {
+            crc = crc16_update(crc, byte);
+        }
+        assert_eq!(crc, 0x29b1);
+    }
+
+    #[test]
+    fn base64_known_vector() {
+        assert_eq!(base64_encode(b"Man"), "TWFu");
+        assert_eq!(base64_decode("TWFu"), Some(b"Man".to_vec()));
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        for data in [vec![1u8], vec![1u8, 2], vec![1u8, 2, 3], vec![9, 8, 7, 6, 5, 255, 0, 128]] {
+            let encoded = base64_encode(&data);
+            assert_eq!(base64_decode(&encoded), Some(data));
+        }
+    }
+
+    #[test]
+    fn base64_decode_rejects_malformed_input() {
+        assert_eq!(base64_decode("not base64!!"), None); // invalid alphabet
+        assert_eq!(base64_decode("abc"), None); // length isn't a multiple of 4
+    }
+
+    #[test]
+    fn export_import_round_trip_preserves_edits() {
+        let strata = vec![LandStratum{texture: LandTexture{spr_id: 1, width: 2, height: 2}, depth: 0}];
+        let mut land = Land::new(5, 5, strata);
+        land.set_seed(42);
+        land.set_circle(16, 16, 6, true);
+        land.set_circle(4, 4, 2, false);
+        land.save_in_map();
+
+        // Snapshot the edited pixels and seed before importing, since the import reuses the
+        // same MAP storage `land` is backed by and would otherwise overwrite it in place
+        let (land_w, land_h) = land.size();
+        let pixels_before: Vec<bool> = (0..land_w)
+            .flat_map(|x| (0..land_h).map(move |y| (x, y)))
+            .map(|(x, y)| land.get(x, y))
+            .collect();
+        let seed_before = land.seed();
+
+        let code = land.export_code();
+        let imported = Land::import_code(&code).expect("a code exported from a valid land should import");
+
+        assert_eq!(imported.seed(), seed_before);
+        let pixels_after: Vec<bool> = (0..land_w)
+            .flat_map(|x| (0..land_h).map(move |y| (x, y)))
+            .map(|(x, y)| imported.get(x, y))
+            .collect();
+        assert_eq!(pixels_after, pixels_before);
+    }
+
+    #[test]
+    fn import_code_rejects_garbage() {
+        assert!(Land::import_code("not a valid code").is_none());
+        assert!(Land::import_code("").is_none());
+    }
+
+    #[test]
+    fn stratum_at_picks_the_deepest_applicable_band() {
+        let strata = vec![
+            LandStratum{texture: LandTexture{spr_id: 1, width: 2, height: 2}, depth: 0},
+            LandStratum{texture: LandTexture{spr_id: 5, width: 2, height: 2}, depth: 4},
+            LandStratum{texture: LandTexture{spr_id: 9, width: 2, height: 2}, depth: 16},
+        ];
+        let land = Land::new(5, 5, strata);
+        assert_eq!(land.stratum_at(0).spr_id, 1);
+        assert_eq!(land.stratum_at(3).spr_id, 1);
+        assert_eq!(land.stratum_at(4).spr_id, 5);
+        assert_eq!(land.stratum_at(15).spr_id, 5);
+        assert_eq!(land.stratum_at(16).spr_id, 9);
+        assert_eq!(land.stratum_at(100).spr_id, 9);
+    }
+
+    #[test]
+    fn higher_cave_density_carves_more_interior_air() {
+        let strata = vec![LandStratum{texture: LandTexture{spr_id: 1, width: 2, height: 2}, depth: 0}];
+        let solid_count = |land: &Land| {
+            let (land_w, land_h) = land.size();
+            (0..land_w).flat_map(|x| (0..land_h).map(move |y| (x, y)))
+                .filter(|&(x, y)| land.get(x, y)).count()
+        };
+
+        let mut solid_land = Land::new(10, 10, strata.clone());
+        solid_land.set_seed(7);
+        solid_land.set_cave_density(0.0);
+        solid_land.generate();
+        // Both lands are backed by the same MAP cells, so count before the second generate()
+        let solid_pixels = solid_count(&solid_land);
+
+        let mut cavey_land = Land::new(10, 10, strata);
+        cavey_land.set_seed(7);
+        cavey_land.set_cave_density(1.0);
+        cavey_land.generate();
+
+        assert!(solid_count(&cavey_land) < solid_pixels);
+    }
+
+    #[test]
+    fn grounded_bridge_spanning_a_dig_stays_grounded() {
+        // A 3px-thick beam anchored to the ground at both ends via pillars. Digging through
+        // its middle leaves two halves, each still on its own pillar, and borders them with
+        // many mutually-adjacent solid pixels at
+        // once; each used to become its own singleton PendingComponent, and since they're all
+        // each other's immediate neighbors, the shared `claimed` set made them block one
+        // another's trace instead of letting it route through to the pillars and ground.
+        let strata = vec![LandStratum{texture: LandTexture{spr_id: 1, width: 2, height: 2}, depth: 0}];
+        let land = Land::new(8, 4, strata);
+        let (land_w, land_h) = land.size();
+
+        // Flat ground along the bottom row
+        for x in 0..land_w {
+            land.set(x, land_h - 1, true);
+        }
+        // Thick beam, 21 columns wide and 3 rows tall
+        for x in 20..41 {
+            for y in 20..23 {
+                land.set(x, y, true);
+            }
+        }
+        // Pillars connecting both beam ends down to the ground
+        for &x in &[20, 40] {
+            for y in 22..land_h {
+                land.set(x, y, true);
+            }
+        }
+
+        land.set_circle(30, 21, 2, false);
+
+        for _ in 0..8 {
+            land.advance_grounded_checks();
+        }
+        assert!(land.pending.borrow().is_empty(), "tracing should finish well within the budget");
+        assert!(land.loose.borrow().is_empty(), "each half of the beam is still on a pillar and shouldn't fall");
+    }
+
+    #[test]
+    fn dig_reconnecting_sea_to_a_sealed_cavern_floods_the_whole_cavern() {
+        // A solid block sits in otherwise-open water, with a cavern hollowed out deep inside it
+        // that has no path to the sea. The incremental recompute used to hard-clip its BFS at a
+        // 1px pad around the dirty rect, so opening a narrow shaft into the cavern only flooded
+        // the rim within the pad and left the rest of the (much bigger) cavern wrongly cached dry.
+        let strata = vec![LandStratum{texture: LandTexture{spr_id: 1, width: 2, height: 2}, depth: 0}];
+        let land = Land::new(8, 6, strata);
+        let (land_w, land_h) = land.size();
+        let water_height = land.water_height();
+
+        // Solid block surrounding & sealing the cavern, standing in otherwise-open water
+        for x in 20..44 {
+            for y in water_height..land_h {
+                land.set(x, y, true);
+            }
+        }
+        // Cavern hollowed out of the bottom of the block, with no way out yet
+        for x in 28..36 {
+            for y in (water_height + 3)..land_h {
+                land.set(x, y, false);
+            }
+        }
+        land.recompute_water_fill();
+        {
+            let water_fill = land.water_fill.borrow();
+            for x in 28..36 {
+                for y in (water_height + 3)..land_h {
+                    assert!(!water_fill.mask[(y * land_w + x) as usize], "cavern should start sealed");
+                }
+            }
+        }
+
+        // Dig a narrow shaft straight down from the open sea into the cavern
+        for y in water_height..(water_height + 3) {
+            land.set(32, y, false);
+        }
+        land.recompute_water_fill();
+
+        let water_fill = land.water_fill.borrow();
+        for x in 28..36 {
+            for y in (water_height + 3)..land_h {
+                assert!(water_fill.mask[(y * land_w + x) as usize],
+                    "cavern pixel ({x}, {y}) should flood once the sea reconnects to it");
+            }
+        }
     }
 }
@@ -5,19 +5,36 @@ mod hud;
 
 use tic80::*;
 use std::cell::{Cell, RefCell};
-use land::{Land, LandTexture};
+use land::{Land, LandTexture, LandStratum};
 
 struct Camera {
     x: i32,
-    y: i32
+    y: i32,
+    target_x: i32, // Land-space point the camera is easing towards centering on
+    target_y: i32,
 }
 
-const LAND_TEXTURE: LandTexture = LandTexture{spr_id: 1, width: 2, height: 2};
+/// Grass -> dirt -> stone, ordered by depth in pixels below the local surface
+fn land_strata() -> Vec<LandStratum> {
+    vec![
+        LandStratum{texture: LandTexture{spr_id: 1, width: 2, height: 2}, depth: 0},
+        LandStratum{texture: LandTexture{spr_id: 5, width: 2, height: 2}, depth: 4},
+        LandStratum{texture: LandTexture{spr_id: 9, width: 2, height: 2}, depth: 16},
+    ]
+}
+
+/// Default amount of interior cave/ravine carving; not persisted in the MAP, so it's
+/// reapplied every boot and can be tuned live with the D-pad
+const DEFAULT_CAVE_DENSITY: f64 = 0.35;
+const CAVE_DENSITY_STEP: f64 = 0.05;
+
 thread_local! {
     static LAND_SEED: Cell<u32> = Cell::new(0);
-    static LAND: RefCell<Land> = RefCell::new(Land::from_map_or_new(45, 24, LAND_TEXTURE));
-    static CAMERA: RefCell<Camera> = RefCell::new(Camera {x: 0, y: 0});
+    static LAND: RefCell<Land> = RefCell::new(Land::from_map_or_new(45, 24, land_strata()));
+    static CAMERA: RefCell<Camera> = RefCell::new(Camera {x: 0, y: 0, target_x: 120, target_y: 68});
     static NOTIFIER: RefCell<hud::Notifier> = RefCell::new(hud::Notifier::default());
+    static CAVE_DENSITY: Cell<f64> = Cell::new(DEFAULT_CAVE_DENSITY);
+    static LAST_CODE: RefCell<String> = RefCell::new(String::new());
 }
 
 const NOTIFY_TIME: i32 = 5*60;
@@ -28,6 +45,8 @@ fn notify(msg: &str) {
 #[export_name = "BOOT"]
 pub fn boot() {
     LAND.with_borrow_mut(|land| {
+        // Cave density isn't persisted in the MAP, so reapply the current setting every boot
+        land.set_cave_density(CAVE_DENSITY.get());
         if land.seed() == 0 {
             land.set_seed(tstamp());
             land.generate();
@@ -71,28 +90,64 @@ pub fn tic() {
         // Button Y: generate new land with the same seed (reset)
         LAND.with_borrow(|land| land.generate());
         notify("Land reset");
+    } else if btn(0) || btn(1) {
+        // D-pad up/down: tune cave density and regenerate with the same seed
+        let step = if btn(0) { CAVE_DENSITY_STEP } else { -CAVE_DENSITY_STEP };
+        let density = CAVE_DENSITY.with(|density| {
+            density.set((density.get() + step).clamp(0.0, 1.0));
+            density.get()
+        });
+        LAND.with_borrow_mut(|land| {
+            land.set_cave_density(density);
+            land.generate();
+        });
+        notify("Cave density adjusted");
+    } else if btn(2) {
+        // Button Left: print a shareable land code to the console so it can be copied out
+        let code = LAND.with_borrow(|land| land.export_code());
+        trace(&code, 11);
+        LAST_CODE.with_borrow_mut(|last| *last = code);
+        notify("Land code printed to console");
+    } else if btn(3) {
+        // Button Right: paste the last exported code back in, proving the round trip works
+        let code = LAST_CODE.with_borrow(|last| last.clone());
+        match Land::import_code(&code) {
+            Some(new_land) => {
+                LAND_SEED.set(new_land.seed());
+                LAND.with_borrow_mut(|land| *land = new_land);
+                notify("Land code imported");
+            }
+            None => notify("No valid land code to import"),
+        }
     }
 
+    // Let terrain disconnected by digging fall
+    LAND.with_borrow(|land| land.step_physics());
+
     // Mouse manipuations & land rendering
     let mouse_input = mouse();
     let (mx, my) = (mouse_input.x as i32, mouse_input.y as i32);
     let radius = 8;
     CAMERA.with_borrow_mut(|cam| {
         LAND.with_borrow(|land| {
-            // Move camera
-            const CAMERA_MOVE_BORDER: i32 = 5;
-            const CAMERA_ADD_MARGIN: (i32, i32, i32, i32) = (75, 75, 50, 25);
-            let (land_w, land_h) = land.size();
-            if mx < CAMERA_MOVE_BORDER && cam.x > -CAMERA_ADD_MARGIN.0 {
-                cam.x -= 1;
-            } else if mx > 240 - CAMERA_MOVE_BORDER && cam.x + 240 < land_w + CAMERA_ADD_MARGIN.1 {
-                cam.x += 1;
-            }
-            if my < CAMERA_MOVE_BORDER && cam.y > -CAMERA_ADD_MARGIN.2 {
-                cam.y -= 1;
-            } else if my > 137 - CAMERA_MOVE_BORDER && cam.y + 137 < land_h + CAMERA_ADD_MARGIN.3 {
-                cam.y += 1;
+            // Retarget the camera on wherever the player is digging/building; the target is
+            // only updated by this deliberate action, not re-derived from the camera's own
+            // offset every frame, so it can't turn into the divergent feedback loop a naive
+            // "recenter on the mouse each frame" update would (each frame's focus would already
+            // bake in the previous frame's correction). Ease the offset towards centering on
+            // that target rather than snapping straight to it, for a smooth follow.
+            if mouse_input.left || mouse_input.right {
+                cam.target_x = mx + cam.x;
+                cam.target_y = my + cam.y;
             }
+            const CAMERA_EASE: i32 = 8;
+            // Integer division alone would truncate the last few pixels of the gap to zero
+            // every frame once it's smaller than CAMERA_EASE, leaving the camera parked short
+            // of the goal forever; snap the remainder instead of dividing it away
+            let ease = |delta: i32| if delta.abs() < CAMERA_EASE { delta } else { delta / CAMERA_EASE };
+            let (goal_x, goal_y) = land.camera_offset(cam.target_x, cam.target_y, 240, 137);
+            cam.x += ease(goal_x - cam.x);
+            cam.y += ease(goal_y - cam.y);
             // Update & render LAND
             if mouse_input.left || mouse_input.right {
                 let (x, y) = (mx + cam.x, my + cam.y);
@@ -105,7 +160,7 @@ pub fn tic() {
 
     // HUD drawing
     circb(mx, my, radius, 2); // Mouse manipulation circle
-    let stats = format!("Seed: {}\nRadius: {}", LAND_SEED.get(), radius);
+    let stats = format!("Seed: {}\nRadius: {}\nCaves: {:.2}", LAND_SEED.get(), radius, CAVE_DENSITY.get());
     print!(stats, 0, 6, PrintOptions::default());
     NOTIFIER.with_borrow_mut(|note| {
         note.countdown();